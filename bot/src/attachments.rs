@@ -0,0 +1,107 @@
+use crate::AtpClient;
+use atrium_api::app::bsky::embed::images::{AspectRatioData, ImageData};
+use atrium_api::types::Union;
+use poise::serenity_prelude::Message;
+use tracing::{debug, warn};
+
+/// PDS blob size limit we forward against; larger attachments fall back to a plain link
+/// instead of failing the whole message.
+const MAX_BLOB_SIZE: usize = 1_000_000;
+
+/// `app.bsky.embed.images` caps its `images` array at 4; attachments beyond that fall back
+/// to a plain link instead of producing an over-length embed.
+const MAX_IMAGES: usize = 4;
+
+pub type Embed = Union<lex::place::stream::chat::message::RecordEmbedRefs>;
+
+/// Result of turning a Discord message's attachments into an SP chat embed: the embed
+/// itself (if at least one image was uploaded) and any extra text to append for
+/// attachments that couldn't be embedded (non-images, or over the blob size limit).
+pub struct AttachmentResult {
+    pub embed: Option<Embed>,
+    pub fallback_text: Option<String>,
+}
+
+/// Download each image attachment, upload it as a blob, and build an embed for them.
+/// Non-image attachments, oversized blobs, and images beyond `MAX_IMAGES` are not
+/// downloaded; they fall back to appending their URL as text.
+pub async fn build_attachment_embed(
+    atp_client: &AtpClient,
+    msg: &Message,
+) -> anyhow::Result<AttachmentResult> {
+    let mut images = Vec::new();
+    let mut fallback_links = Vec::new();
+
+    for attachment in &msg.attachments {
+        let is_image = attachment
+            .content_type
+            .as_deref()
+            .is_some_and(|content_type| content_type.starts_with("image/"));
+
+        if !is_image {
+            debug!(filename = %attachment.filename, "Non-image attachment, falling back to URL");
+            fallback_links.push(attachment.url.clone());
+            continue;
+        }
+
+        if attachment.size as usize > MAX_BLOB_SIZE {
+            warn!(
+                filename = %attachment.filename,
+                size = attachment.size,
+                "Attachment exceeds blob size limit, falling back to URL"
+            );
+            fallback_links.push(attachment.url.clone());
+            continue;
+        }
+
+        if images.len() >= MAX_IMAGES {
+            debug!(
+                filename = %attachment.filename,
+                "Embed already has the maximum of {MAX_IMAGES} images, falling back to URL"
+            );
+            fallback_links.push(attachment.url.clone());
+            continue;
+        }
+
+        let bytes = reqwest::get(&attachment.url).await?.bytes().await?;
+
+        let output = atp_client
+            .api
+            .com
+            .atproto
+            .repo
+            .upload_blob(bytes.to_vec())
+            .await?;
+
+        images.push(
+            ImageData {
+                image: output.data.blob,
+                alt: attachment.filename.clone(),
+                aspect_ratio: attachment_aspect_ratio(&attachment.filename),
+            }
+            .into(),
+        );
+    }
+
+    let embed = if images.is_empty() {
+        None
+    } else {
+        Some(Union::Refs(
+            lex::place::stream::chat::message::RecordEmbedRefs::AppBskyEmbedImagesMain(Box::new(
+                atrium_api::app::bsky::embed::images::MainData { images }.into(),
+            )),
+        ))
+    };
+
+    let fallback_text = (!fallback_links.is_empty()).then(|| fallback_links.join(" "));
+
+    Ok(AttachmentResult {
+        embed,
+        fallback_text,
+    })
+}
+
+/// We don't decode image dimensions here, so leave the aspect ratio unset.
+fn attachment_aspect_ratio(_filename: &str) -> Option<atrium_api::types::Object<AspectRatioData>> {
+    None
+}
@@ -0,0 +1,81 @@
+use crate::{Context, Error};
+
+/// Manage which Discord channels are bridged to SP chat
+#[poise::command(
+    slash_command,
+    subcommands("add", "remove", "list"),
+    required_permissions = "ADMINISTRATOR",
+    guild_only
+)]
+pub async fn bridge(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Bridge this channel to a streamer's SP chat
+#[poise::command(slash_command, required_permissions = "ADMINISTRATOR")]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "SP streamer DID to bridge this channel to"] streamer_did: String,
+) -> Result<(), Error> {
+    let channel_id = ctx.channel_id().to_string();
+
+    ctx.data()
+        .storage
+        .add_channel_mapping(&channel_id, &streamer_did)
+        .await?;
+    ctx.data()
+        .channel_mappings
+        .write()
+        .await
+        .insert(channel_id, streamer_did.clone());
+    ctx.data().mapping_changed.notify_one();
+
+    ctx.say(format!("Bridging this channel to `{streamer_did}`"))
+        .await?;
+    Ok(())
+}
+
+/// Remove this channel's SP chat bridge
+#[poise::command(slash_command, required_permissions = "ADMINISTRATOR")]
+pub async fn remove(ctx: Context<'_>) -> Result<(), Error> {
+    let channel_id = ctx.channel_id().to_string();
+
+    let removed = ctx
+        .data()
+        .storage
+        .remove_channel_mapping(&channel_id)
+        .await?;
+    ctx.data()
+        .channel_mappings
+        .write()
+        .await
+        .remove(&channel_id);
+    ctx.data().mapping_changed.notify_one();
+
+    if removed {
+        ctx.say("Removed this channel's bridge").await?;
+    } else {
+        ctx.say("This channel wasn't bridged").await?;
+    }
+    Ok(())
+}
+
+/// List every bridged channel
+#[poise::command(slash_command, required_permissions = "ADMINISTRATOR")]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let mappings = ctx.data().channel_mappings.read().await;
+
+    if mappings.is_empty() {
+        ctx.say("No channels are bridged").await?;
+        return Ok(());
+    }
+
+    let mut lines: Vec<String> = mappings
+        .iter()
+        .map(|(channel_id, streamer_did)| format!("<#{channel_id}> -> `{streamer_did}`"))
+        .collect();
+    lines.sort();
+
+    ctx.say(lines.join("\n")).await?;
+    Ok(())
+}
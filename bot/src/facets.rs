@@ -0,0 +1,187 @@
+use atrium_api::app::bsky::richtext::facet::Main as Facet;
+use atrium_api::app::bsky::richtext::facet::{
+    ByteSliceData, LinkData, MainData, MainFeaturesItem, MentionData, TagData,
+};
+use std::collections::HashMap;
+
+/// Build `app.bsky.richtext.facet` entries for links, hashtags, and known mentions in `text`.
+///
+/// `index.byte_start`/`byte_end` are UTF-8 *byte* offsets, so this must run against the
+/// final, fully-substituted message text rather than the raw Discord content.
+/// `known_mentions` maps the `@DisplayName` text already substituted into `text` to the
+/// ATProto DID it resolves to, for Discord users we know on ATProto.
+pub fn build_facets(
+    text: &str,
+    known_mentions: &HashMap<String, String>,
+) -> anyhow::Result<Vec<Facet>> {
+    let mut spans: Vec<(usize, usize, MainFeaturesItem)> = Vec::new();
+
+    for (start, end, uri) in find_links(text) {
+        spans.push((
+            start,
+            end,
+            MainFeaturesItem::Link(Box::new(LinkData { uri }.into())),
+        ));
+    }
+
+    for (start, end, tag) in find_hashtags(text) {
+        spans.push((
+            start,
+            end,
+            MainFeaturesItem::Tag(Box::new(TagData { tag }.into())),
+        ));
+    }
+
+    for (display_name, did) in known_mentions {
+        for (start, end) in find_mentions(text, display_name) {
+            spans.push((
+                start,
+                end,
+                MainFeaturesItem::Mention(Box::new(MentionData { did: did.parse()? }.into())),
+            ));
+        }
+    }
+
+    dedupe_overlapping(&mut spans);
+
+    spans
+        .into_iter()
+        .map(|(byte_start, byte_end, feature)| {
+            Ok(MainData {
+                index: ByteSliceData {
+                    byte_start,
+                    byte_end,
+                }
+                .into(),
+                features: vec![feature],
+            }
+            .into())
+        })
+        .collect()
+}
+
+/// Drop later spans that overlap an earlier (already-kept) span so no two facets cover
+/// the same bytes.
+fn dedupe_overlapping(spans: &mut Vec<(usize, usize, MainFeaturesItem)>) {
+    spans.sort_by_key(|(start, _, _)| *start);
+    let mut kept: Vec<(usize, usize, MainFeaturesItem)> = Vec::with_capacity(spans.len());
+    for (start, end, feature) in spans.drain(..) {
+        if kept
+            .iter()
+            .any(|(k_start, k_end, _)| start < *k_end && *k_start < end)
+        {
+            continue;
+        }
+        kept.push((start, end, feature));
+    }
+    *spans = kept;
+}
+
+/// Find `http(s)://` URLs in `text`, returning `(byte_start, byte_end, uri)`.
+fn find_links(text: &str) -> Vec<(usize, usize, String)> {
+    let mut links = Vec::new();
+    for (start, _) in text.match_indices("http") {
+        if !text[start..].starts_with("http://") && !text[start..].starts_with("https://") {
+            continue;
+        }
+        let mut end = text[start..]
+            .find(|c: char| c.is_whitespace())
+            .map(|offset| start + offset)
+            .unwrap_or(text.len());
+        // Trim common trailing punctuation that isn't part of the URL.
+        while end > start && text[..end].ends_with(['.', ',', ')', '>', '!', '?']) {
+            end -= 1;
+        }
+        links.push((start, end, text[start..end].to_string()));
+    }
+    links
+}
+
+/// Find `#hashtag` occurrences in `text`, returning `(byte_start, byte_end, tag)`.
+fn find_hashtags(text: &str) -> Vec<(usize, usize, String)> {
+    let mut tags = Vec::new();
+    for (start, _) in text.match_indices('#') {
+        let rest = &text[start + 1..];
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            continue;
+        }
+        let end = start + 1 + len;
+        tags.push((start, end, text[start + 1..end].to_string()));
+    }
+    tags
+}
+
+/// Find every occurrence of `@display_name` in `text`, returning `(byte_start, byte_end)`.
+/// Anchored at the end the same way `find_hashtags` is, so `@Ann` doesn't also match the
+/// first four bytes of `@Anna`.
+fn find_mentions(text: &str, display_name: &str) -> Vec<(usize, usize)> {
+    let needle = format!("@{display_name}");
+    let mut occurrences = Vec::new();
+    let mut offset = 0;
+    while let Some(pos) = text[offset..].find(needle.as_str()) {
+        let start = offset + pos;
+        let end = start + needle.len();
+        let at_word_boundary = !text[end..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+        if at_word_boundary {
+            occurrences.push((start, end));
+        }
+        offset = end;
+    }
+    occurrences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mention_does_not_match_longer_name_sharing_its_prefix() {
+        let text = "Hello @Anna and @Ann";
+        let mut known_mentions = HashMap::new();
+        known_mentions.insert("Ann".to_string(), "did:plc:ann".to_string());
+        known_mentions.insert("Anna".to_string(), "did:plc:anna".to_string());
+
+        let facets = build_facets(text, &known_mentions).unwrap();
+
+        let mentions: Vec<(usize, usize, String)> = facets
+            .iter()
+            .map(|facet| {
+                let did = match &facet.features[0] {
+                    MainFeaturesItem::Mention(mention) => mention.did.to_string(),
+                    other => panic!("expected a mention facet, got {other:?}"),
+                };
+                (facet.index.byte_start, facet.index.byte_end, did)
+            })
+            .collect();
+
+        assert_eq!(mentions.len(), 2);
+        assert!(mentions.contains(&(6, 11, "did:plc:anna".to_string())));
+        assert!(mentions.contains(&(16, 20, "did:plc:ann".to_string())));
+    }
+
+    #[test]
+    fn link_with_fragment_wins_over_nested_hashtag() {
+        let text = "see http://example.com#section here";
+        let facets = build_facets(text, &HashMap::new()).unwrap();
+
+        assert_eq!(facets.len(), 1);
+        match &facets[0].features[0] {
+            MainFeaturesItem::Link(link) => assert_eq!(link.uri, "http://example.com#section"),
+            other => panic!("expected a link facet, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn link_trims_trailing_punctuation() {
+        let text = "Check this out: http://example.com.";
+        let links = find_links(text);
+
+        assert_eq!(links, vec![(16, 34, "http://example.com".to_string())]);
+    }
+}
@@ -1,8 +1,15 @@
-use crate::{AtpClient, Error};
+use crate::attachments;
+use crate::facets::{self, Facet};
+use crate::metrics;
+use crate::storage::{ForwardedRecord, Storage};
+use crate::{AtpClient, Error, RecentRecordUris};
 use anyhow::anyhow;
+use atrium_api::types::string::Tid;
 use atrium_api::types::Unknown;
-use poise::serenity_prelude::{Context, Message};
+use poise::serenity_prelude::{Context, Message, MessageId};
 use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use tracing::{debug, info, instrument, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
@@ -20,6 +27,9 @@ pub async fn forward_message(
     ctx: &Context,
     msg: &Message,
     atp_client: &AtpClient,
+    storage: &Storage,
+    channel_mappings: &HashMap<String, String>,
+    own_record_uris: &Arc<Mutex<RecentRecordUris>>,
 ) -> Result<(), Error> {
     // Add some completely unnecessary but enterprise-grade telemetry attributes 🎭
     let current_span = Span::current();
@@ -35,9 +45,19 @@ pub async fn forward_message(
     current_span.set_attribute("business.criticality", "mission_critical"); // Obviously 😎
     current_span.set_attribute("team.on_call", "chaos_engineering");
 
+    let channel_id = msg.channel_id.to_string();
+    metrics::metrics()
+        .messages_received
+        .with_label_values(&[&channel_id])
+        .inc();
+
     // Skip messages from bots or that start with command prefix
     if msg.author.bot || msg.content.starts_with("~") {
         current_span.set_attribute("skip.reason", "bot_or_command");
+        metrics::metrics()
+            .messages_skipped
+            .with_label_values(&["bot_or_command"])
+            .inc();
         tracing::debug!(
             reason = "Bot message or command prefix detected",
             author_is_bot = msg.author.bot,
@@ -47,11 +67,24 @@ pub async fn forward_message(
     }
 
     // Convert Discord message content to SP chat format
-    let message_text = format_discord_message(ctx, msg).await?;
+    let (mut message_text, message_facets) = format_discord_message(ctx, msg).await?;
+
+    // Upload any image attachments as blobs and embed them; anything that can't be
+    // embedded (non-images, oversized blobs) falls back to its raw URL in the text.
+    let attachment_result = attachments::build_attachment_embed(atp_client, msg).await?;
+    if let Some(fallback_text) = &attachment_result.fallback_text {
+        message_text = format!("{message_text} {fallback_text}").trim().to_string();
+    }
+    current_span.set_attribute("sp.has_embed", attachment_result.embed.is_some());
 
-    // Skip empty messages (e.g., just attachments without text)
-    if message_text.trim().is_empty() {
+    // Skip messages with neither text nor an embed (a failed attachment upload returns an
+    // error above instead of reaching this check)
+    if message_text.trim().is_empty() && attachment_result.embed.is_none() {
         current_span.set_attribute("skip.reason", "empty_content");
+        metrics::metrics()
+            .messages_skipped
+            .with_label_values(&["empty_content"])
+            .inc();
         tracing::debug!(
             reason = "Empty message content after formatting",
             "Skipping empty message"
@@ -66,7 +99,7 @@ pub async fn forward_message(
     );
 
     // Get the streamer DID for this channel
-    let streamer_did = get_streamer_for_channel(&msg.channel_id.to_string())?;
+    let streamer_did = get_streamer_for_channel(channel_mappings, &channel_id)?;
     current_span.set_attribute("sp.streamer_did", streamer_did.clone());
     current_span.set_attribute("sp.protocol", "atproto");
     current_span.set_attribute("sp.collection", "place.stream.chat.message");
@@ -77,12 +110,25 @@ pub async fn forward_message(
     let session = atp_client.get_session().await.ok_or("No active session")?;
     current_span.set_attribute("atp.session_did", session.did.to_string());
 
+    // If this is a reply to a message we previously forwarded, carry over its thread
+    // reference; otherwise post as a top-level message (e.g. the parent predates the bridge).
+    let parent_record = resolve_reply_parent(storage, msg).await?;
+    if parent_record.is_none() && msg.referenced_message.is_some() {
+        debug!("Reply parent was never forwarded, posting as top-level message");
+    }
+    if let Some(parent_record) = &parent_record {
+        current_span.set_attribute("sp.reply_parent_uri", parent_record.uri.clone());
+    }
+
+    let reply = parent_record.as_ref().map(build_reply_ref).transpose()?;
+
     let chat_message = lex::place::stream::chat::message::RecordData {
         text: message_text.clone(),
         created_at: atrium_api::types::string::Datetime::now(),
         streamer: streamer_did.parse()?,
-        facets: None,
-        reply: None,
+        facets: (!message_facets.is_empty()).then_some(message_facets),
+        embed: attachment_result.embed,
+        reply: reply.map(Into::into),
     };
 
     debug!(
@@ -99,6 +145,11 @@ pub async fn forward_message(
     // Convert to Unknown using serde deserialization
     let record_unknown: Unknown = serde_json::from_value(serde_json::to_value(&chat_message)?)?;
 
+    // Generate our own rkey (rather than letting the PDS pick one) so we can later
+    // putRecord/deleteRecord against it when the Discord source is edited or removed.
+    let rkey = Tid::now(Default::default()).to_string();
+    current_span.set_attribute("atp.rkey", rkey.clone());
+
     tracing::debug!(
         operation = "com.atproto.repo.createRecord",
         endpoint = "create_record",
@@ -106,6 +157,7 @@ pub async fn forward_message(
         "api_call_starting"
     );
     debug!("Making API call to create record");
+    let create_record_timer = metrics::metrics().create_record_latency.start_timer();
     let result = atp_client
         .api
         .com
@@ -116,7 +168,7 @@ pub async fn forward_message(
                 repo: session.did.clone().into(),
                 collection: "place.stream.chat.message".parse()?,
                 record: record_unknown,
-                rkey: None,
+                rkey: Some(rkey.clone().parse()?),
                 // do not validate as PDSes can't resolve lexicons yet
                 validate: Some(false),
                 swap_commit: None,
@@ -124,6 +176,11 @@ pub async fn forward_message(
             .into(),
         )
         .await?;
+    create_record_timer.observe_duration();
+    metrics::metrics()
+        .messages_forwarded
+        .with_label_values(&[&channel_id])
+        .inc();
 
     // Record success metrics and attributes (because why not track EVERYTHING! 📈)
     current_span.set_attribute("atp.record_uri", result.uri.clone());
@@ -131,6 +188,30 @@ pub async fn forward_message(
     current_span.set_attribute("operation.success", true);
     current_span.set_attribute("sla.performance_tier", "premium"); // We're fancy! ✨
 
+    // Remember that this is our own record so the Jetstream bridge doesn't echo it back.
+    own_record_uris.lock().await.insert(result.uri.clone());
+
+    // A top-level message is the root of its own thread; a reply inherits its parent's root.
+    let (root_uri, root_cid) = match &parent_record {
+        Some(parent_record) => (
+            parent_record.root_uri.clone(),
+            parent_record.root_cid.clone(),
+        ),
+        None => (result.uri.clone(), format!("{:?}", result.cid)),
+    };
+
+    // Persist the mapping so a later Discord edit/delete can putRecord/deleteRecord on it.
+    storage
+        .record_forwarded_message(
+            msg.id.get(),
+            &rkey,
+            &result.uri,
+            &format!("{:?}", result.cid),
+            &root_uri,
+            &root_cid,
+        )
+        .await?;
+
     tracing::debug!(
         destination = "sp_chat",
         record_uri = result.uri.clone(),
@@ -148,19 +229,150 @@ pub async fn forward_message(
     Ok(())
 }
 
-/// Format a Discord message for posting to SP chat
+/// Mirror a Discord message edit onto its SP chat record, if it was forwarded.
+#[instrument(skip(ctx, atp_client, storage), fields(message_id = %msg.id))]
+pub async fn update_message(
+    ctx: &Context,
+    msg: &Message,
+    atp_client: &AtpClient,
+    storage: &Storage,
+    channel_mappings: &HashMap<String, String>,
+) -> Result<(), Error> {
+    let Some(existing) = storage.get_forwarded_message(msg.id.get()).await? else {
+        debug!("Edited message was never forwarded, nothing to update");
+        return Ok(());
+    };
+
+    let (mut message_text, message_facets) = format_discord_message(ctx, msg).await?;
+    let streamer_did = get_streamer_for_channel(channel_mappings, &msg.channel_id.to_string())?;
+    let session = atp_client.get_session().await.ok_or("No active session")?;
+
+    // put_record replaces the whole record, so the embed and reply ref have to be
+    // rebuilt here exactly as forward_message does, or an edit would silently strip them.
+    let attachment_result = attachments::build_attachment_embed(atp_client, msg).await?;
+    if let Some(fallback_text) = &attachment_result.fallback_text {
+        message_text = format!("{message_text} {fallback_text}").trim().to_string();
+    }
+    let reply = resolve_reply_parent(storage, msg)
+        .await?
+        .as_ref()
+        .map(build_reply_ref)
+        .transpose()?;
+
+    let chat_message = lex::place::stream::chat::message::RecordData {
+        text: message_text.clone(),
+        created_at: atrium_api::types::string::Datetime::now(),
+        streamer: streamer_did.parse()?,
+        facets: (!message_facets.is_empty()).then_some(message_facets),
+        embed: attachment_result.embed,
+        reply: reply.map(Into::into),
+    };
+    let record_unknown: Unknown = serde_json::from_value(serde_json::to_value(&chat_message)?)?;
+
+    let result = atp_client
+        .api
+        .com
+        .atproto
+        .repo
+        .put_record(
+            atrium_api::com::atproto::repo::put_record::InputData {
+                repo: session.did.clone().into(),
+                collection: "place.stream.chat.message".parse()?,
+                rkey: existing.rkey.clone(),
+                record: record_unknown,
+                validate: Some(false),
+                swap_commit: None,
+                swap_record: None,
+            }
+            .into(),
+        )
+        .await?;
+
+    // A top-level message is its own thread root, so its root ref follows the new CID.
+    let new_cid = format!("{:?}", result.cid);
+    let (root_uri, root_cid) = if existing.root_uri == existing.uri {
+        (result.uri.clone(), new_cid.clone())
+    } else {
+        (existing.root_uri.clone(), existing.root_cid.clone())
+    };
+
+    storage
+        .record_forwarded_message(
+            msg.id.get(),
+            &existing.rkey,
+            &result.uri,
+            &new_cid,
+            &root_uri,
+            &root_cid,
+        )
+        .await?;
+
+    info!(uri = %result.uri, "Updated SP chat record for edited Discord message");
+
+    Ok(())
+}
+
+/// Delete the SP chat record for a Discord message that was removed, if it was forwarded.
+#[instrument(skip(atp_client, storage), fields(%message_id))]
+pub async fn delete_message(
+    message_id: MessageId,
+    atp_client: &AtpClient,
+    storage: &Storage,
+) -> Result<(), Error> {
+    let Some(existing) = storage.get_forwarded_message(message_id.get()).await? else {
+        debug!("Deleted message was never forwarded, nothing to remove");
+        return Ok(());
+    };
+
+    let session = atp_client.get_session().await.ok_or("No active session")?;
+
+    atp_client
+        .api
+        .com
+        .atproto
+        .repo
+        .delete_record(
+            atrium_api::com::atproto::repo::delete_record::InputData {
+                repo: session.did.clone().into(),
+                collection: "place.stream.chat.message".parse()?,
+                rkey: existing.rkey.clone(),
+                swap_commit: None,
+                swap_record: None,
+            }
+            .into(),
+        )
+        .await?;
+
+    storage.delete_forwarded_message(message_id.get()).await?;
+
+    info!(rkey = %existing.rkey, "Deleted SP chat record for removed Discord message");
+
+    Ok(())
+}
+
+/// Format a Discord message for posting to SP chat, along with the richtext facets
+/// (links, hashtags, known mentions) computed against the final formatted text.
 #[instrument(skip(ctx), fields(content_length = msg.content.len()))]
-async fn format_discord_message(ctx: &Context, msg: &Message) -> anyhow::Result<String> {
+async fn format_discord_message(
+    ctx: &Context,
+    msg: &Message,
+) -> anyhow::Result<(String, Vec<Facet>)> {
     let mut content = msg.content.clone();
 
-    if content.trim().is_empty() {
+    if content.trim().is_empty() && msg.attachments.is_empty() {
         return Err(anyhow!("no content found!"));
     }
 
+    let known_atproto_users = get_known_atproto_users();
+    let mut known_mentions = HashMap::new();
+
     // Handle mentions - convert Discord mentions to readable format
     for user in &msg.mentions {
         let mention_pattern = format!("<@{}>", user.id);
-        let display_name = user.display_name();
+        let display_name = user.display_name().to_string();
+        if let Some(did) = known_atproto_users.get(&user.id.to_string()) {
+            known_mentions.insert(display_name.clone(), did.clone());
+        }
         content = content.replace(&mention_pattern, &format!("@{}", display_name));
     }
 
@@ -191,34 +403,66 @@ async fn format_discord_message(ctx: &Context, msg: &Message) -> anyhow::Result<
         format!("{} {}", author_info, content.trim())
     };
 
+    // Facets are byte-range annotations over the final text, so compute them last.
+    let message_facets = facets::build_facets(&formatted, &known_mentions)?;
+
     // Return the formatted message (SP chat may have different limits than Bluesky)
-    Ok(formatted)
+    Ok((formatted, message_facets))
 }
 
-/// Get channel mappings from environment or configuration
-/// Format: "discord_channel_id=streamer_did,another_id=another_did"
-pub fn get_channel_mappings() -> HashMap<String, String> {
-    let mut mappings = HashMap::new();
+/// Discord user IDs known to have a corresponding ATProto DID, so their mentions can be
+/// tagged with an `app.bsky.richtext.facet#mention` feature instead of staying plain text.
+/// Format: "discord_user_id=did,another_user_id=another_did"
+fn get_known_atproto_users() -> HashMap<String, String> {
+    let mut known = HashMap::new();
 
-    if let Ok(mapping_str) = std::env::var("CHANNEL_MAPPINGS") {
-        // Expected format: "discord_channel_id=streamer_did,another_id=another_did"
-        // Using = as delimiter since DIDs contain colons (e.g., did:web:my.ball)
+    if let Ok(mapping_str) = std::env::var("KNOWN_ATPROTO_USERS") {
         for pair in mapping_str.split(',') {
             let parts: Vec<&str> = pair.split('=').collect();
             if parts.len() == 2 {
-                mappings.insert(parts[0].trim().to_string(), parts[1].trim().to_string());
+                known.insert(parts[0].trim().to_string(), parts[1].trim().to_string());
             }
         }
-    } else {
-        println!("No CHANNEL_MAPPINGS environment variable set");
     }
 
-    mappings
+    known
+}
+
+/// If `msg` is a reply to a message we previously forwarded, look up the SP chat record
+/// it was posted as; otherwise (not a reply, or the parent was never forwarded) `None`.
+async fn resolve_reply_parent(
+    storage: &Storage,
+    msg: &Message,
+) -> anyhow::Result<Option<ForwardedRecord>> {
+    match &msg.referenced_message {
+        Some(parent) => storage.get_forwarded_message(parent.id.get()).await,
+        None => Ok(None),
+    }
+}
+
+/// Build a `ReplyRefData` pointing at `parent_record`'s record and thread root.
+fn build_reply_ref(
+    parent_record: &ForwardedRecord,
+) -> anyhow::Result<lex::place::stream::chat::message::ReplyRefData> {
+    Ok(lex::place::stream::chat::message::ReplyRefData {
+        parent: atrium_api::com::atproto::repo::strong_ref::MainData {
+            cid: parent_record.cid.parse()?,
+            uri: parent_record.uri.clone(),
+        }
+        .into(),
+        root: atrium_api::com::atproto::repo::strong_ref::MainData {
+            cid: parent_record.root_cid.parse()?,
+            uri: parent_record.root_uri.clone(),
+        }
+        .into(),
+    })
 }
 
-/// Get the streamer DID for a given Discord channel
-fn get_streamer_for_channel(channel_id: &str) -> Result<String, Error> {
-    let mappings = get_channel_mappings();
+/// Get the streamer DID mapped to a given Discord channel
+fn get_streamer_for_channel(
+    mappings: &HashMap<String, String>,
+    channel_id: &str,
+) -> Result<String, Error> {
     mappings
         .get(channel_id)
         .cloned()
@@ -226,8 +470,6 @@ fn get_streamer_for_channel(channel_id: &str) -> Result<String, Error> {
 }
 
 /// Check if a channel should be forwarded to SP chat
-pub fn should_forward_channel(channel_id: &str) -> bool {
-    let mappings = get_channel_mappings();
-    println!("Channel mappings: {:?}", mappings);
+pub fn should_forward_channel(mappings: &HashMap<String, String>, channel_id: &str) -> bool {
     mappings.contains_key(channel_id)
 }
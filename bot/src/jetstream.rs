@@ -0,0 +1,177 @@
+use crate::{metrics, Data, Error};
+use futures_util::StreamExt;
+use poise::serenity_prelude::{ChannelId, Http};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tracing::{debug, error, info, warn};
+
+const JETSTREAM_ENDPOINT: &str = "wss://jetstream2.us-east.bsky.network/subscribe";
+const CHAT_COLLECTION: &str = "place.stream.chat.message";
+
+#[derive(Debug, Deserialize)]
+struct JetstreamEvent {
+    did: String,
+    time_us: i64,
+    commit: Option<JetstreamCommit>,
+}
+
+/// The subset of a Jetstream event we need to advance `cursor`, parsed independently of
+/// the rest of the event so a commit/record body we can't deserialize doesn't also block
+/// cursor advancement (which would otherwise replay everything since on every reconnect).
+#[derive(Debug, Deserialize)]
+struct JetstreamCursor {
+    time_us: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct JetstreamCommit {
+    operation: String,
+    collection: String,
+    rkey: String,
+    record: Option<serde_json::Value>,
+}
+
+/// Invert `Data::channel_mappings` (streamer DID -> Discord channel) for the reverse path.
+async fn reverse_channel_mappings(data: &Data) -> HashMap<String, String> {
+    data.channel_mappings
+        .read()
+        .await
+        .iter()
+        .map(|(channel_id, streamer_did)| (streamer_did.clone(), channel_id.clone()))
+        .collect()
+}
+
+/// Subscribe to Jetstream for `place.stream.chat.message` on every mapped streamer DID and
+/// relay records created by other clients into the matching Discord channel.
+///
+/// Runs forever, reconnecting with exponential backoff and resuming from the last seen
+/// `time_us` cursor so a dropped connection doesn't silently drop messages.
+pub async fn run(http: Arc<Http>, data: Arc<Data>) {
+    let mut cursor: Option<i64> = None;
+    let mut backoff = Duration::from_secs(1);
+
+    loop {
+        match connect_and_relay(&http, &data, &mut cursor).await {
+            Ok(()) => {
+                warn!("Jetstream connection closed, reconnecting");
+                backoff = Duration::from_secs(1);
+            }
+            Err(e) => {
+                error!(error = %e, "Jetstream connection failed, reconnecting");
+                backoff = (backoff * 2).min(Duration::from_secs(60));
+            }
+        }
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+async fn connect_and_relay(
+    http: &Arc<Http>,
+    data: &Arc<Data>,
+    cursor: &mut Option<i64>,
+) -> anyhow::Result<()> {
+    let mappings = reverse_channel_mappings(data).await;
+    if mappings.is_empty() {
+        debug!("No channel mappings configured, nothing to bridge back from SP chat");
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        return Ok(());
+    }
+
+    let mut url = format!("{JETSTREAM_ENDPOINT}?wantedCollections={CHAT_COLLECTION}");
+    for did in mappings.keys() {
+        url.push_str(&format!("&wantedDids={did}"));
+    }
+    if let Some(cursor) = cursor {
+        url.push_str(&format!("&cursor={cursor}"));
+    }
+
+    info!(url = %url, "Connecting to Jetstream");
+    let (ws_stream, _) = tokio_tungstenite::connect_async(url).await?;
+    let (_, mut read) = ws_stream.split();
+
+    loop {
+        let msg = tokio::select! {
+            msg = read.next() => match msg {
+                Some(msg) => msg?,
+                None => break,
+            },
+            _ = data.mapping_changed.notified() => {
+                info!("Channel mappings changed, reconnecting Jetstream with fresh wantedDids");
+                return Ok(());
+            }
+        };
+
+        let WsMessage::Text(text) = msg else {
+            continue;
+        };
+
+        // Advance the cursor as soon as we know time_us, before attempting to parse the
+        // full event, so a record we can't deserialize doesn't freeze the cursor forever.
+        match serde_json::from_str::<JetstreamCursor>(&text) {
+            Ok(envelope) => *cursor = Some(envelope.time_us),
+            Err(e) => {
+                debug!(error = %e, "Failed to parse Jetstream cursor envelope, skipping");
+                continue;
+            }
+        }
+
+        let event: JetstreamEvent = match serde_json::from_str(&text) {
+            Ok(event) => event,
+            Err(e) => {
+                debug!(error = %e, "Failed to parse Jetstream event, skipping");
+                continue;
+            }
+        };
+
+        let Some(commit) = event.commit else {
+            continue;
+        };
+        if commit.operation != "create" || commit.collection != CHAT_COLLECTION {
+            continue;
+        }
+        let Some(channel_id) = mappings.get(&event.did) else {
+            continue;
+        };
+
+        let uri = format!("at://{}/{}/{}", event.did, commit.collection, commit.rkey);
+        if data.own_record_uris.lock().await.contains(&uri) {
+            debug!(uri = %uri, "Skipping our own record");
+            continue;
+        }
+
+        let Some(text) = commit
+            .record
+            .as_ref()
+            .and_then(|record| record.get("text"))
+            .and_then(|v| v.as_str())
+        else {
+            continue;
+        };
+
+        if let Err(e) = relay_to_discord(http, channel_id, &event.did, text).await {
+            metrics::metrics()
+                .messages_failed
+                .with_label_values(&[channel_id])
+                .inc();
+            error!(error = %e, channel_id = %channel_id, "Failed to relay SP chat message to Discord");
+        }
+    }
+
+    Ok(())
+}
+
+async fn relay_to_discord(
+    http: &Arc<Http>,
+    channel_id: &str,
+    streamer_did: &str,
+    text: &str,
+) -> Result<(), Error> {
+    let channel_id: ChannelId = channel_id.parse::<u64>()?.into();
+    channel_id
+        .say(http, format!("**{streamer_did}** (SP chat): {text}"))
+        .await?;
+    Ok(())
+}
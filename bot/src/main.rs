@@ -1,4 +1,6 @@
-use ::serenity::all::{EventHandler, GatewayIntents, Message};
+use ::serenity::all::{
+    ChannelId, EventHandler, GatewayIntents, GuildId, Message, MessageId, MessageUpdateEvent,
+};
 use ::serenity::prelude::TypeMapKey;
 use atrium_api::agent::atp_agent::store::MemorySessionStore;
 use atrium_api::agent::atp_agent::AtpAgent;
@@ -9,24 +11,75 @@ use opentelemetry_otlp::{OtlpExporterPipeline, WithExportConfig};
 use opentelemetry_sdk::{trace as sdktrace, Resource};
 use opentelemetry_semantic_conventions as semconv;
 use poise::serenity_prelude as serenity;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
 use std::sync::Arc;
+use tokio::sync::{Mutex, Notify, RwLock};
 use tracing::{error, info};
 use tracing_subscriber::prelude::*;
 
+mod attachments;
+mod commands;
+mod facets;
 mod fwd;
+mod jetstream;
+mod metrics;
+mod storage;
+
+use storage::Storage;
 
 type AtpClient = AtpAgent<MemorySessionStore, ReqwestClient>;
 
 struct Data {
     atp: AtpClient,
+    storage: Storage,
+    /// Discord channel ID -> streamer DID, loaded from `storage` at startup and kept in
+    /// sync at runtime by the `/bridge` slash commands.
+    channel_mappings: Arc<RwLock<HashMap<String, String>>>,
+    /// URIs of records we posted ourselves, so the Jetstream bridge doesn't echo them back.
+    own_record_uris: Arc<Mutex<RecentRecordUris>>,
+    /// Signaled by the `/bridge` slash commands so the Jetstream subscription (which is
+    /// keyed to the streamer DIDs in `channel_mappings` at connect time) reconnects with
+    /// an up to date `wantedDids` filter instead of waiting for its next natural reconnect.
+    mapping_changed: Arc<Notify>,
 }
 
 impl TypeMapKey for Data {
     type Value = Arc<Data>;
 }
+
+/// How many recently-forwarded record URIs to remember. The only race this guards
+/// against (Jetstream delivering our own record back to us before we've moved on) is a
+/// tight one, so a fixed-size window is plenty without needing a TTL.
+const RECENT_RECORD_URIS_CAP: usize = 256;
+
+/// A size-bounded cache of record URIs, oldest-evicted, so `Data::own_record_uris` doesn't
+/// grow unboundedly over the life of the bridge process.
+#[derive(Default)]
+struct RecentRecordUris {
+    order: VecDeque<String>,
+    set: HashSet<String>,
+}
+
+impl RecentRecordUris {
+    fn insert(&mut self, uri: String) {
+        if !self.set.insert(uri.clone()) {
+            return;
+        }
+        self.order.push_back(uri);
+        if self.order.len() > RECENT_RECORD_URIS_CAP {
+            if let Some(oldest) = self.order.pop_front() {
+                self.set.remove(&oldest);
+            }
+        }
+    }
+
+    fn contains(&self, uri: &str) -> bool {
+        self.set.contains(uri)
+    }
+}
 type Error = Box<dyn std::error::Error + Send + Sync>;
-//type Context<'a> = poise::Context<'a, Arc<Data>, Error>;
+type Context<'a> = poise::Context<'a, Arc<Data>, Error>;
 
 // Event handler
 struct Handler;
@@ -35,23 +88,114 @@ struct Handler;
 impl EventHandler for Handler {
     async fn message(&self, ctx: serenity::prelude::Context, msg: Message) {
         println!("Received message in {:?}: {}", msg.channel_id, msg.content);
+        let data = ctx.data.read().await;
+        let Some(bot_data) = data.get::<Data>().cloned() else {
+            return;
+        };
+        drop(data);
+
+        let mappings = bot_data.channel_mappings.read().await.clone();
+
         // Check if this channel should be forwarded to Bluesky
-        if fwd::should_forward_channel(&msg.channel_id.to_string()) {
+        if fwd::should_forward_channel(&mappings, &msg.channel_id.to_string()) {
             println!("Forwarding message to Bluesky...");
-            let data = ctx.data.read().await;
-            if let Some(bot_data) = data.get::<Data>() {
-                println!("Found bot data, forwarding...");
-                if let Err(e) = fwd::forward_message(&ctx, &msg, &bot_data.atp).await {
-                    error!(
-                        error = %e,
-                        channel_id = %msg.channel_id,
-                        author = %msg.author.name,
-                        "Failed to forward message to SP chat"
-                    );
-                }
+            if let Err(e) = fwd::forward_message(
+                &ctx,
+                &msg,
+                &bot_data.atp,
+                &bot_data.storage,
+                &mappings,
+                &bot_data.own_record_uris,
+            )
+            .await
+            {
+                metrics::metrics()
+                    .messages_failed
+                    .with_label_values(&[&msg.channel_id.to_string()])
+                    .inc();
+                error!(
+                    error = %e,
+                    channel_id = %msg.channel_id,
+                    author = %msg.author.name,
+                    "Failed to forward message to SP chat"
+                );
             }
         }
     }
+
+    async fn message_update(
+        &self,
+        ctx: serenity::prelude::Context,
+        _old_if_available: Option<Message>,
+        new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        let Some(msg) = new else {
+            // The updated message is only available when the original was in the cache;
+            // without it there's nothing to rebuild the record from.
+            return;
+        };
+
+        let data = ctx.data.read().await;
+        let Some(bot_data) = data.get::<Data>().cloned() else {
+            return;
+        };
+        drop(data);
+
+        let mappings = bot_data.channel_mappings.read().await.clone();
+        if !fwd::should_forward_channel(&mappings, &event.channel_id.to_string()) {
+            return;
+        }
+
+        if let Err(e) =
+            fwd::update_message(&ctx, &msg, &bot_data.atp, &bot_data.storage, &mappings).await
+        {
+            metrics::metrics()
+                .messages_failed
+                .with_label_values(&[&event.channel_id.to_string()])
+                .inc();
+            error!(
+                error = %e,
+                channel_id = %event.channel_id,
+                message_id = %event.id,
+                "Failed to update SP chat record for edited message"
+            );
+        }
+    }
+
+    async fn message_delete(
+        &self,
+        ctx: serenity::prelude::Context,
+        channel_id: ChannelId,
+        deleted_message_id: MessageId,
+        _guild_id: Option<GuildId>,
+    ) {
+        let data = ctx.data.read().await;
+        let Some(bot_data) = data.get::<Data>().cloned() else {
+            return;
+        };
+        drop(data);
+
+        let mappings = bot_data.channel_mappings.read().await.clone();
+        if !fwd::should_forward_channel(&mappings, &channel_id.to_string()) {
+            return;
+        }
+
+        if let Err(e) =
+            fwd::delete_message(deleted_message_id, &bot_data.atp, &bot_data.storage).await
+        {
+            metrics::metrics()
+                .messages_failed
+                .with_label_values(&[&channel_id.to_string()])
+                .inc();
+            error!(
+                error = %e,
+                channel_id = %channel_id,
+                message_id = %deleted_message_id,
+                "Failed to delete SP chat record for removed message"
+            );
+        }
+    }
 }
 
 async fn setup_atp_sess() -> anyhow::Result<AtpAgent<MemorySessionStore, ReqwestClient>> {
@@ -152,13 +296,31 @@ async fn main() {
         .await
         .expect("Failed to set up ATP session");
 
-    let user_data = Arc::new(Data { atp });
+    let database_url =
+        env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite://bridge.db?mode=rwc".to_string());
+    let storage = Storage::connect(&database_url)
+        .await
+        .expect("Failed to set up bridge storage");
+
+    let channel_mappings = storage
+        .load_channel_mappings()
+        .await
+        .expect("Failed to load channel mappings");
+    info!(count = channel_mappings.len(), "Loaded channel mappings");
+
+    let user_data = Arc::new(Data {
+        atp,
+        storage,
+        channel_mappings: Arc::new(RwLock::new(channel_mappings)),
+        own_record_uris: Arc::new(Mutex::new(RecentRecordUris::default())),
+        mapping_changed: Arc::new(Notify::new()),
+    });
 
     let intents = GatewayIntents::non_privileged() | GatewayIntents::MESSAGE_CONTENT;
     let ud_clone = user_data.clone();
     let framework = poise::Framework::<Arc<Data>, Error>::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![],
+            commands: vec![commands::bridge()],
             ..Default::default()
         })
         .setup(|ctx, _ready, framework| {
@@ -182,8 +344,17 @@ async fn main() {
 
     {
         let mut data = client.data.write().await;
-        data.insert::<Data>(user_data);
+        data.insert::<Data>(user_data.clone());
     }
 
+    // Bridge SP chat -> Discord in the other direction, alongside the Discord -> SP forwarder.
+    tokio::spawn(jetstream::run(client.http.clone(), user_data));
+
+    let metrics_port: u16 = env::var("METRICS_PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(9090);
+    tokio::spawn(metrics::run(metrics_port));
+
     client.start().await.unwrap();
 }
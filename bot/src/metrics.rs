@@ -0,0 +1,130 @@
+use axum::http::{header, HeaderName, StatusCode};
+use axum::routing::get;
+use axum::Router;
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounterVec, Opts, Registry, TextEncoder};
+use std::sync::OnceLock;
+use tracing::{error, info};
+
+/// Counters and histograms for the bridge, scraped via the `/metrics` HTTP endpoint.
+///
+/// `messages_received`/`forwarded`/`failed` are labeled by `channel_id` so operators get
+/// per-channel throughput and error-rate visibility, not just bridge-wide totals.
+pub struct Metrics {
+    registry: Registry,
+    pub messages_received: IntCounterVec,
+    pub messages_forwarded: IntCounterVec,
+    pub messages_skipped: IntCounterVec,
+    pub messages_failed: IntCounterVec,
+    pub create_record_latency: Histogram,
+}
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let messages_received = IntCounterVec::new(
+            Opts::new(
+                "bridge_messages_received_total",
+                "Discord messages observed by the bridge, by channel",
+            ),
+            &["channel_id"],
+        )
+        .expect("valid metric");
+        let messages_forwarded = IntCounterVec::new(
+            Opts::new(
+                "bridge_messages_forwarded_total",
+                "Messages successfully forwarded to SP chat, by channel",
+            ),
+            &["channel_id"],
+        )
+        .expect("valid metric");
+        let messages_skipped = IntCounterVec::new(
+            Opts::new(
+                "bridge_messages_skipped_total",
+                "Messages skipped before forwarding, by reason",
+            ),
+            &["reason"],
+        )
+        .expect("valid metric");
+        let messages_failed = IntCounterVec::new(
+            Opts::new(
+                "bridge_messages_failed_total",
+                "Messages that failed to forward, update, delete, or relay, by channel",
+            ),
+            &["channel_id"],
+        )
+        .expect("valid metric");
+        let create_record_latency = Histogram::with_opts(HistogramOpts::new(
+            "bridge_create_record_duration_seconds",
+            "Latency of com.atproto.repo.createRecord calls",
+        ))
+        .expect("valid metric");
+
+        registry
+            .register(Box::new(messages_received.clone()))
+            .expect("register metric");
+        registry
+            .register(Box::new(messages_forwarded.clone()))
+            .expect("register metric");
+        registry
+            .register(Box::new(messages_skipped.clone()))
+            .expect("register metric");
+        registry
+            .register(Box::new(messages_failed.clone()))
+            .expect("register metric");
+        registry
+            .register(Box::new(create_record_latency.clone()))
+            .expect("register metric");
+
+        Self {
+            registry,
+            messages_received,
+            messages_forwarded,
+            messages_skipped,
+            messages_failed,
+            create_record_latency,
+        }
+    }
+}
+
+/// The process-wide metrics registry, initialized on first use.
+pub fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::new)
+}
+
+async fn serve_metrics() -> (StatusCode, [(HeaderName, &'static str); 1], Vec<u8>) {
+    let encoder = TextEncoder::new();
+    let metric_families = metrics().registry.gather();
+
+    let mut buffer = Vec::new();
+    encoder
+        .encode(&metric_families, &mut buffer)
+        .expect("encode metrics");
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, encoder.format_type())],
+        buffer,
+    )
+}
+
+/// Serve `/metrics` in the Prometheus text exposition format on `port`.
+pub async fn run(port: u16) {
+    let app = Router::new().route("/metrics", get(serve_metrics));
+    let addr = format!("0.0.0.0:{port}");
+
+    let listener = match tokio::net::TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!(error = %e, %addr, "Failed to bind metrics server");
+            return;
+        }
+    };
+
+    info!(%addr, "Serving Prometheus metrics");
+    if let Err(e) = axum::serve(listener, app).await {
+        error!(error = %e, "Metrics server exited");
+    }
+}
@@ -0,0 +1,160 @@
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+
+/// The SP chat record a forwarded Discord message was posted as, including the root of
+/// the reply thread it belongs to (itself, if it's a top-level message).
+#[derive(Debug, Clone)]
+pub struct ForwardedRecord {
+    pub rkey: String,
+    pub uri: String,
+    pub cid: String,
+    pub root_uri: String,
+    pub root_cid: String,
+}
+
+/// Persistent state for the bridge: which SP chat record backs which Discord message,
+/// so edits and deletes on the Discord side can be mirrored instead of only forwarding creates.
+pub struct Storage {
+    pool: SqlitePool,
+}
+
+impl Storage {
+    pub async fn connect(database_url: &str) -> anyhow::Result<Self> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect(database_url)
+            .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS forwarded_messages (
+                discord_message_id TEXT PRIMARY KEY,
+                rkey                TEXT NOT NULL,
+                uri                 TEXT NOT NULL,
+                cid                 TEXT NOT NULL,
+                root_uri            TEXT NOT NULL,
+                root_cid            TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS channel_mappings (
+                discord_channel_id TEXT PRIMARY KEY,
+                streamer_did        TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        Ok(Self { pool })
+    }
+
+    /// Record that `discord_message_id` was forwarded as the given SP chat record.
+    /// `root_uri`/`root_cid` should be the record's own URI/CID for top-level messages,
+    /// or the thread root's for replies.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn record_forwarded_message(
+        &self,
+        discord_message_id: u64,
+        rkey: &str,
+        uri: &str,
+        cid: &str,
+        root_uri: &str,
+        root_cid: &str,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO forwarded_messages \
+             (discord_message_id, rkey, uri, cid, root_uri, root_cid) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(discord_message_id.to_string())
+        .bind(rkey)
+        .bind(uri)
+        .bind(cid)
+        .bind(root_uri)
+        .bind(root_cid)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Look up the SP chat record a previously forwarded Discord message was posted as.
+    pub async fn get_forwarded_message(
+        &self,
+        discord_message_id: u64,
+    ) -> anyhow::Result<Option<ForwardedRecord>> {
+        let row = sqlx::query_as::<_, (String, String, String, String, String)>(
+            "SELECT rkey, uri, cid, root_uri, root_cid FROM forwarded_messages \
+             WHERE discord_message_id = ?",
+        )
+        .bind(discord_message_id.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(
+            row.map(|(rkey, uri, cid, root_uri, root_cid)| ForwardedRecord {
+                rkey,
+                uri,
+                cid,
+                root_uri,
+                root_cid,
+            }),
+        )
+    }
+
+    /// Forget a forwarded Discord message, e.g. once its SP chat record has been deleted.
+    pub async fn delete_forwarded_message(&self, discord_message_id: u64) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM forwarded_messages WHERE discord_message_id = ?")
+            .bind(discord_message_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Bridge a Discord channel to a streamer's SP chat (replacing any existing mapping).
+    pub async fn add_channel_mapping(
+        &self,
+        discord_channel_id: &str,
+        streamer_did: &str,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO channel_mappings (discord_channel_id, streamer_did) \
+             VALUES (?, ?)",
+        )
+        .bind(discord_channel_id)
+        .bind(streamer_did)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remove a channel's bridge mapping. Returns whether a mapping existed.
+    pub async fn remove_channel_mapping(&self, discord_channel_id: &str) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM channel_mappings WHERE discord_channel_id = ?")
+            .bind(discord_channel_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Load every channel mapping, e.g. to seed the in-memory cache at startup.
+    pub async fn load_channel_mappings(&self) -> anyhow::Result<HashMap<String, String>> {
+        let rows = sqlx::query_as::<_, (String, String)>(
+            "SELECT discord_channel_id, streamer_did FROM channel_mappings",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().collect())
+    }
+}